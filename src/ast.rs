@@ -4,30 +4,43 @@
 // Defines the AST structure and evaluation logic
 // ===========================================
 
-use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_complex::Complex;
+
+use crate::compiler::{Instruction, UnaryFn};
+use crate::env::{Environment, FunctionDef};
+use crate::error::CalcError;
 
 /// Core trait for all AST nodes - enables polymorphic evaluation
 pub trait AstNode {
-    /// Evaluates the node and returns optional f64 result
-    fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64>;
+    /// Evaluates the node and returns the resulting value, or the error that stopped it
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError>;
+
+    /// Lowers this node into bytecode via a post-order walk, appending to `out`
+    fn compile(&self, out: &mut Vec<Instruction>);
 }
 
 // ==================== NUMERIC NODE ====================
-/// Represents a numeric literal (e.g., 42, 3.14)
+/// Represents a numeric literal (e.g., 42, 3.14, 3i)
 pub struct NumberNode {
-    pub value: f64,
+    pub value: Complex<f64>,
 }
 
 impl AstNode for NumberNode {
-    fn evaluate(&self, _env: &mut HashMap<String, f64>) -> Option<f64> {
-        Some(self.value) // Always returns the stored numeric value
+    fn evaluate(&self, _env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        Ok(self.value) // Always returns the stored numeric value
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        out.push(Instruction::Push(self.value));
     }
 }
 
 // ==================== BINARY OPERATIONS ====================
 /// Macro to generate binary operation nodes (reduces code duplication)
 macro_rules! binary_operation {
-    ($struct_name:ident, $operator:tt) => {
+    ($struct_name:ident, $operator:tt, $instruction:ident) => {
         /// Binary operation node with left and right children
         pub struct $struct_name {
             pub left: Box<dyn AstNode>,
@@ -35,19 +48,48 @@ macro_rules! binary_operation {
         }
 
         impl AstNode for $struct_name {
-            fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
+            fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
                 // Use ? operator for automatic error propagation
-                Some(self.left.evaluate(env)? $operator self.right.evaluate(env)?)
+                Ok(self.left.evaluate(env)? $operator self.right.evaluate(env)?)
+            }
+
+            fn compile(&self, out: &mut Vec<Instruction>) {
+                self.left.compile(out);
+                self.right.compile(out);
+                out.push(Instruction::$instruction);
             }
         }
     };
 }
 
 // Generate common binary operation nodes
-binary_operation!(AddNode, +);
-binary_operation!(SubtractNode, -);
-binary_operation!(MultiplyNode, *);
-binary_operation!(DivideNode, /);
+binary_operation!(AddNode, +, Add);
+binary_operation!(SubtractNode, -, Sub);
+binary_operation!(MultiplyNode, *, Mul);
+
+// ==================== DIVISION ====================
+/// Division node - checked separately so it can report `DivisionByZero`
+pub struct DivideNode {
+    pub left: Box<dyn AstNode>,
+    pub right: Box<dyn AstNode>,
+}
+
+impl AstNode for DivideNode {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        let left = self.left.evaluate(env)?;
+        let right = self.right.evaluate(env)?;
+        if right == Complex::new(0.0, 0.0) {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(left / right)
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.left.compile(out);
+        self.right.compile(out);
+        out.push(Instruction::Div);
+    }
+}
 
 // ==================== POWER OPERATION ====================
 /// Exponentiation node (base ^ exponent)
@@ -57,32 +99,152 @@ pub struct PowerNode {
 }
 
 impl AstNode for PowerNode {
-    fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
-        Some(self.base.evaluate(env)?.powf(self.exponent.evaluate(env)?))
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        let base = self.base.evaluate(env)?;
+        let exponent = self.exponent.evaluate(env)?;
+        // Real base raised to a real exponent: fall back to `powf` for exact
+        // results - but only when `powf` itself stays real (non-negative base,
+        // or an integer exponent). A negative base with a fractional exponent
+        // (e.g. `(-8)^(1/3)`) must go through `powc` for the correct principal
+        // complex root; `powf` would just return `NaN`.
+        Ok(if base.im == 0.0 && exponent.im == 0.0 && (base.re >= 0.0 || exponent.re.fract() == 0.0) {
+            Complex::new(base.re.powf(exponent.re), 0.0)
+        } else {
+            base.powc(exponent)
+        })
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.base.compile(out);
+        self.exponent.compile(out);
+        out.push(Instruction::Pow);
+    }
+}
+
+// ==================== COMPARISON OPERATIONS ====================
+/// Macro to generate comparison nodes; each evaluates to 1.0 (true) or 0.0 (false).
+/// `Complex<f64>` has no natural ordering, so `$compare` decides how two
+/// values compare - ordering operators look at the real part only, while
+/// equality compares the full value.
+macro_rules! comparison_operation {
+    ($struct_name:ident, $compare:expr, $instruction:ident) => {
+        /// Comparison node with left and right children
+        pub struct $struct_name {
+            pub left: Box<dyn AstNode>,
+            pub right: Box<dyn AstNode>,
+        }
+
+        impl AstNode for $struct_name {
+            fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+                let left = self.left.evaluate(env)?;
+                let right = self.right.evaluate(env)?;
+                let compare: fn(Complex<f64>, Complex<f64>) -> bool = $compare;
+                Ok(if compare(left, right) { Complex::new(1.0, 0.0) } else { Complex::new(0.0, 0.0) })
+            }
+
+            fn compile(&self, out: &mut Vec<Instruction>) {
+                self.left.compile(out);
+                self.right.compile(out);
+                out.push(Instruction::$instruction);
+            }
+        }
+    };
+}
+
+comparison_operation!(LessThanNode, |a, b| a.re < b.re, Less);
+comparison_operation!(GreaterThanNode, |a, b| a.re > b.re, Greater);
+comparison_operation!(LessEqualNode, |a, b| a.re <= b.re, LessEqual);
+comparison_operation!(GreaterEqualNode, |a, b| a.re >= b.re, GreaterEqual);
+comparison_operation!(EqualsEqualsNode, |a, b| a == b, Equal);
+
+// ==================== CONDITIONAL ====================
+/// Node for `if cond then a else b`
+pub struct IfNode {
+    pub condition: Box<dyn AstNode>,
+    pub then_branch: Box<dyn AstNode>,
+    pub else_branch: Box<dyn AstNode>,
+}
+
+impl AstNode for IfNode {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        if self.condition.evaluate(env)? != Complex::new(0.0, 0.0) {
+            self.then_branch.evaluate(env)
+        } else {
+            self.else_branch.evaluate(env)
+        }
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.condition.compile(out);
+
+        let jump_if_zero_at = out.len();
+        out.push(Instruction::JumpIfZero(0)); // target patched in below
+
+        self.then_branch.compile(out);
+
+        let jump_over_else_at = out.len();
+        out.push(Instruction::Jump(0)); // target patched in below
+
+        let else_start = out.len();
+        out[jump_if_zero_at] = Instruction::JumpIfZero(else_start);
+
+        self.else_branch.compile(out);
+
+        let end = out.len();
+        out[jump_over_else_at] = Instruction::Jump(end);
+    }
+}
+
+// ==================== UNARY NEGATION ====================
+/// Prefix negation node (e.g., -x, -(a + b))
+pub struct NegateNode {
+    pub operand: Box<dyn AstNode>,
+}
+
+impl AstNode for NegateNode {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        let negated = -self.operand.evaluate(env)?;
+        // Negating a zero component yields -0.0, which flips the branch cut
+        // `sqrt`/`powc` use for negative reals (e.g. `sqrt(-1)` would give
+        // `0 - 1i` instead of the principal value `0 + 1i`) - normalize it away
+        Ok(Complex::new(
+            if negated.re == 0.0 { 0.0 } else { negated.re },
+            if negated.im == 0.0 { 0.0 } else { negated.im },
+        ))
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.operand.compile(out);
+        out.push(Instruction::Neg);
     }
 }
 
 // ==================== UNARY FUNCTIONS ====================
 /// Macro to generate unary mathematical function nodes
 macro_rules! unary_function {
-    ($struct_name:ident, $function:expr) => {
+    ($struct_name:ident, $function:expr, $unary_fn:ident) => {
         /// Unary function node with single argument
         pub struct $struct_name {
             pub argument: Box<dyn AstNode>,
         }
 
         impl AstNode for $struct_name {
-            fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
-                Some($function(self.argument.evaluate(env)?))
+            fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+                Ok($function(self.argument.evaluate(env)?))
+            }
+
+            fn compile(&self, out: &mut Vec<Instruction>) {
+                self.argument.compile(out);
+                out.push(Instruction::Call(UnaryFn::$unary_fn));
             }
         }
     };
 }
 
 // Generate mathematical function nodes
-unary_function!(SineNode, f64::sin);
-unary_function!(CosineNode, f64::cos);
-unary_function!(SquareRootNode, f64::sqrt);
+unary_function!(SineNode, Complex::sin, Sin);
+unary_function!(CosineNode, Complex::cos, Cos);
+unary_function!(SquareRootNode, Complex::sqrt, Sqrt);
 
 // ==================== OUTPUT OPERATION ====================
 /// Node that prints result to console while evaluating
@@ -91,10 +253,16 @@ pub struct PrintNode {
 }
 
 impl AstNode for PrintNode {
-    fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
         let result = self.argument.evaluate(env)?;
-        println!("=> {}", result); // Display result with prompt-like format
-        Some(result)
+        println!("=> {}", crate::format::format_complex(result)); // Display result with prompt-like format
+        env.printed = true;
+        Ok(result)
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.argument.compile(out);
+        out.push(Instruction::Print);
     }
 }
 
@@ -105,21 +273,166 @@ pub struct VariableNode {
 }
 
 impl AstNode for VariableNode {
-    fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
-        env.get(&self.name).copied() // Lookup variable in environment
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        env.variables
+            .get(&self.name)
+            .copied()
+            .ok_or_else(|| CalcError::UndefinedVariable(self.name.clone()))
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        out.push(Instruction::Load(self.name.clone()));
     }
 }
 
-/// Node for variable assignment (e.g., let x = 5)
+/// Node for variable assignment (e.g., x = 5)
 pub struct AssignmentNode {
     pub variable_name: String,
     pub value: Box<dyn AstNode>,
 }
 
 impl AstNode for AssignmentNode {
-    fn evaluate(&self, env: &mut HashMap<String, f64>) -> Option<f64> {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
         let computed_value = self.value.evaluate(env)?;
-        env.insert(self.variable_name.clone(), computed_value);
-        Some(computed_value)
+        env.variables.insert(self.variable_name.clone(), computed_value);
+        Ok(computed_value)
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        self.value.compile(out);
+        // Leave the stored value on the stack so the expression still yields it
+        out.push(Instruction::Store(self.variable_name.clone()));
+    }
+}
+
+// ==================== USER-DEFINED FUNCTIONS ====================
+/// Node for a function definition (e.g., `square(x) = x^2`)
+pub struct FunctionDefNode {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Rc<dyn AstNode>,
+}
+
+impl AstNode for FunctionDefNode {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        env.functions.insert(
+            self.name.clone(),
+            Rc::new(FunctionDef { params: self.params.clone(), body: Rc::clone(&self.body) }),
+        );
+        Ok(Complex::new(0.0, 0.0)) // Defining a function has no meaningful scalar result
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        out.push(Instruction::DefineFunction {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: Rc::clone(&self.body),
+        });
+    }
+}
+
+/// Node for calling a user-defined function (e.g., `square(3)`, `hypot(a, b)`)
+pub struct FunctionCallNode {
+    pub name: String,
+    pub args: Vec<Box<dyn AstNode>>,
+}
+
+impl AstNode for FunctionCallNode {
+    fn evaluate(&self, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        let function = env
+            .functions
+            .get(&self.name)
+            .cloned()
+            .ok_or_else(|| CalcError::UnknownFunction(self.name.clone()))?;
+
+        if self.args.len() != function.params.len() {
+            return Err(CalcError::ArityMismatch {
+                name: self.name.clone(),
+                expected: function.params.len(),
+                found: self.args.len(),
+            });
+        }
+
+        // Bind each argument to its parameter name in a fresh scope layered over the
+        // caller's variables: the globals for a top-level call, and for a call made
+        // from inside a function body also that function's parameters
+        let mut locals = env.variables.clone();
+        for (param, arg) in function.params.iter().zip(&self.args) {
+            locals.insert(param.clone(), arg.evaluate(env)?);
+        }
+
+        let mut call_env = Environment { variables: locals, functions: env.functions.clone(), printed: false };
+        let result = function.body.evaluate(&mut call_env)?;
+        env.printed |= call_env.printed;
+        Ok(result)
+    }
+
+    fn compile(&self, out: &mut Vec<Instruction>) {
+        for arg in &self.args {
+            arg.compile(out);
+        }
+        out.push(Instruction::CallFunction { name: self.name.clone(), arg_count: self.args.len() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: f64) -> Box<dyn AstNode> {
+        Box::new(NumberNode { value: Complex::new(value, 0.0) })
+    }
+
+    #[test]
+    fn real_power_is_exact() {
+        let node = PowerNode { base: number(3.0), exponent: number(2.0) };
+        let mut env = Environment::new();
+        assert_eq!(node.evaluate(&mut env).unwrap(), Complex::new(9.0, 0.0));
+    }
+
+    #[test]
+    fn negative_base_fractional_power_is_not_nan() {
+        // (-8)^(1/3): a negative real base with a fractional exponent must
+        // route through `powc` for the principal complex root, not `powf`
+        // (which would return NaN here)
+        let node = PowerNode { base: number(-8.0), exponent: number(1.0 / 3.0) };
+        let mut env = Environment::new();
+        let result = node.evaluate(&mut env).unwrap();
+        assert!(!result.re.is_nan() && !result.im.is_nan(), "expected a complex root, got NaN: {:?}", result);
+        assert!(result.im > 0.0, "expected the principal root with a positive imaginary part, got {:?}", result);
+    }
+
+    #[test]
+    fn negating_a_real_number_normalizes_signed_zero() {
+        let node = NegateNode { operand: number(1.0) };
+        let mut env = Environment::new();
+        let result = node.evaluate(&mut env).unwrap();
+        assert_eq!(result, Complex::new(-1.0, 0.0));
+        assert!(result.im.is_sign_positive(), "negating a real value must not leave a -0.0 imaginary part");
+    }
+
+    #[test]
+    fn square_root_of_negated_literal_matches_principal_value() {
+        let node = SquareRootNode { argument: Box::new(NegateNode { operand: number(1.0) }) };
+        let mut env = Environment::new();
+        assert_eq!(node.evaluate(&mut env).unwrap(), Complex::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn bytecode_vm_matches_tree_walking_evaluation() {
+        // (2 + 3) ^ 2
+        let tree: Box<dyn AstNode> = Box::new(PowerNode {
+            base: Box::new(AddNode { left: number(2.0), right: number(3.0) }),
+            exponent: number(2.0),
+        });
+
+        let mut tree_env = Environment::new();
+        let tree_result = tree.evaluate(&mut tree_env).unwrap();
+
+        let instructions = crate::compiler::compile(&*tree);
+        let mut vm_env = Environment::new();
+        let vm_result = crate::vm::Vm::new().run(&instructions, &mut vm_env).unwrap();
+
+        assert_eq!(tree_result, vm_result);
     }
 }
@@ -0,0 +1,89 @@
+// ===========================================
+// COMPILER MODULE
+// ===========================================
+// Lowers an AST into a flat instruction stream for the stack VM
+// ===========================================
+
+use std::rc::Rc;
+
+use num_complex::Complex;
+
+use crate::ast::AstNode;
+
+/// Built-in unary math functions callable from compiled code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryFn {
+    Sin,
+    Cos,
+    Sqrt,
+}
+
+/// A single stack-machine instruction
+#[derive(Clone)]
+pub enum Instruction {
+    Push(Complex<f64>),
+    Load(String),
+    Store(String),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Neg,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    Call(UnaryFn),
+    Print,
+    /// Unconditional jump to an absolute instruction index
+    Jump(usize),
+    /// Pops the top of the stack; jumps to the target if it's zero
+    JumpIfZero(usize),
+    /// Defines a user function; its body stays a tree and is evaluated
+    /// directly (rather than further compiled) when the function is called
+    DefineFunction {
+        name: String,
+        params: Vec<String>,
+        body: Rc<dyn AstNode>,
+    },
+    /// Calls a user function, popping `arg_count` arguments off the stack
+    CallFunction { name: String, arg_count: usize },
+}
+
+/// Compiles an AST into a flat instruction stream via a post-order walk
+pub fn compile(ast: &dyn AstNode) -> Vec<Instruction> {
+    let mut out = Vec::new();
+    ast.compile(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AddNode, NumberNode};
+    use num_complex::Complex;
+
+    fn number(value: f64) -> Box<dyn AstNode> {
+        Box::new(NumberNode { value: Complex::new(value, 0.0) })
+    }
+
+    #[test]
+    fn number_compiles_to_a_single_push() {
+        let instructions = compile(&*number(42.0));
+        assert_eq!(instructions.len(), 1);
+        assert!(matches!(instructions[0], Instruction::Push(value) if value == Complex::new(42.0, 0.0)));
+    }
+
+    #[test]
+    fn addition_compiles_in_post_order() {
+        let tree = AddNode { left: number(1.0), right: number(2.0) };
+        let instructions = compile(&tree);
+
+        assert_eq!(instructions.len(), 3);
+        assert!(matches!(instructions[0], Instruction::Push(value) if value == Complex::new(1.0, 0.0)));
+        assert!(matches!(instructions[1], Instruction::Push(value) if value == Complex::new(2.0, 0.0)));
+        assert!(matches!(instructions[2], Instruction::Add));
+    }
+}
@@ -0,0 +1,47 @@
+// ===========================================
+// ENVIRONMENT MODULE
+// ===========================================
+// Runtime state shared across evaluation: scalar variables and
+// user-defined functions
+// ===========================================
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_complex::Complex;
+
+use crate::ast::AstNode;
+
+/// A user-defined function (e.g. `square(x) = x^2`)
+pub struct FunctionDef {
+    pub params: Vec<String>,
+    pub body: Rc<dyn AstNode>,
+}
+
+/// Calculator runtime state: scalar variables live in their own map since
+/// functions (which close over parameter names, not values) can't be
+/// stored alongside them
+pub struct Environment {
+    pub variables: HashMap<String, Complex<f64>>,
+    pub functions: HashMap<String, Rc<FunctionDef>>,
+    /// Set by `PrintNode`/`Instruction::Print` when an evaluation already
+    /// echoed its own result, so callers don't print it a second time
+    pub printed: bool,
+}
+
+impl Environment {
+    /// Creates an empty environment
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            printed: false,
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,104 @@
+// ===========================================
+// ERROR TYPES MODULE
+// ===========================================
+// Structured diagnostics for lexing, parsing, and evaluation failures
+// ===========================================
+
+use std::fmt;
+
+use crate::token::Token;
+
+/// Everything that can go wrong while lexing, parsing, or evaluating a line
+/// of input. Lexer and parser errors carry the source position they point at;
+/// evaluation errors carry only the offending name or counts, if any
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError {
+    UnexpectedChar { ch: char, pos: usize },
+    InvalidNumber { text: String, pos: usize },
+    UnexpectedToken { found: Token, expected: Option<Token>, pos: usize },
+    UnknownFunction(String),
+    UndefinedVariable(String),
+    DivisionByZero,
+    ArityMismatch { name: String, expected: usize, found: usize },
+}
+
+impl CalcError {
+    /// Source position this error points at, if it has one
+    pub fn pos(&self) -> Option<usize> {
+        match self {
+            CalcError::UnexpectedChar { pos, .. }
+            | CalcError::InvalidNumber { pos, .. }
+            | CalcError::UnexpectedToken { pos, .. } => Some(*pos),
+            CalcError::UnknownFunction(_)
+            | CalcError::UndefinedVariable(_)
+            | CalcError::DivisionByZero
+            | CalcError::ArityMismatch { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::UnexpectedChar { ch, pos } => {
+                write!(f, "unexpected character '{}' at position {}", ch, pos)
+            }
+            CalcError::InvalidNumber { text, pos } => {
+                write!(f, "invalid number '{}' at position {}", text, pos)
+            }
+            CalcError::UnexpectedToken { found, expected: Some(expected), pos } => {
+                write!(f, "expected {:?}, found {:?} at position {}", expected, found, pos)
+            }
+            CalcError::UnexpectedToken { found, expected: None, pos } => {
+                write!(f, "unexpected token {:?} at position {}", found, pos)
+            }
+            CalcError::UnknownFunction(name) => write!(f, "unknown function: {}", name),
+            CalcError::UndefinedVariable(name) => write!(f, "undefined variable: {}", name),
+            CalcError::DivisionByZero => write!(f, "division by zero"),
+            CalcError::ArityMismatch { name, expected, found } => {
+                write!(f, "{} expects {} argument(s), found {}", name, expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CalcError {}
+
+/// Renders `input` followed by a line with a caret under `pos`, e.g.:
+///
+/// ```text
+/// sin(x +
+///         ^
+/// ```
+pub fn render_with_caret(input: &str, pos: usize) -> String {
+    let marker: String = std::iter::repeat_n(' ', pos).chain(std::iter::once('^')).collect();
+    format!("{}\n{}", input, marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caret_points_at_the_given_position() {
+        assert_eq!(render_with_caret("1 + x", 4), "1 + x\n    ^");
+    }
+
+    #[test]
+    fn caret_at_start_of_input() {
+        assert_eq!(render_with_caret("+1", 0), "+1\n^");
+    }
+
+    #[test]
+    fn unexpected_char_reports_position() {
+        let error = CalcError::UnexpectedChar { ch: '@', pos: 3 };
+        assert_eq!(error.pos(), Some(3));
+        assert_eq!(error.to_string(), "unexpected character '@' at position 3");
+    }
+
+    #[test]
+    fn errors_without_source_context_have_no_position() {
+        assert_eq!(CalcError::DivisionByZero.pos(), None);
+        assert_eq!(CalcError::UndefinedVariable("x".to_string()).pos(), None);
+    }
+}
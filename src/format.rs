@@ -0,0 +1,39 @@
+// ===========================================
+// RESULT FORMATTING MODULE
+// ===========================================
+// Renders a complex value the way the REPL prints it
+// ===========================================
+
+use num_complex::Complex;
+
+/// Formats a complex value as `a + bi`, omitting the imaginary part
+/// entirely when it's zero (e.g. `4` instead of `4 + 0i`)
+pub fn format_complex(value: Complex<f64>) -> String {
+    if value.im == 0.0 {
+        format!("{}", value.re)
+    } else if value.im < 0.0 {
+        format!("{} - {}i", value.re, -value.im)
+    } else {
+        format!("{} + {}i", value.re, value.im)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn real_value_omits_imaginary_part() {
+        assert_eq!(format_complex(Complex::new(4.0, 0.0)), "4");
+    }
+
+    #[test]
+    fn positive_imaginary_part_uses_plus() {
+        assert_eq!(format_complex(Complex::new(3.0, 4.0)), "3 + 4i");
+    }
+
+    #[test]
+    fn negative_imaginary_part_uses_minus() {
+        assert_eq!(format_complex(Complex::new(3.0, -4.0)), "3 - 4i");
+    }
+}
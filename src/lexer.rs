@@ -5,6 +5,7 @@
 // ===========================================
 
 use super::token::Token;
+use crate::error::CalcError;
 
 pub struct Lexer {
     input: Vec<char>,    // Source code as character vector
@@ -20,11 +21,12 @@ impl Lexer {
         }
     }
 
-    /// Reads and returns next token from input
-    pub fn next_token(&mut self) -> Result<Token, String> {
+    /// Reads and returns the next token along with its starting position
+    pub fn next_token(&mut self) -> Result<(Token, usize), CalcError> {
         self.skip_whitespace();
-        
-        match self.current_char() {
+        let start = self.position;
+
+        let token = match self.current_char() {
             Some(c) => match c {
                 // Single-character tokens
                 '(' => self.consume(Token::LeftParenthesis),
@@ -34,23 +36,28 @@ impl Lexer {
                 '*' => self.consume(Token::Multiply),
                 '/' => self.consume(Token::Divide),
                 '^' => self.consume(Token::Caret),
-                '=' => self.consume(Token::Equals),
-                
+                ',' => self.consume(Token::Comma),
+                '<' => self.consume_with_optional_equals(Token::Less, Token::LessEqual),
+                '>' => self.consume_with_optional_equals(Token::Greater, Token::GreaterEqual),
+                '=' => self.consume_with_optional_equals(Token::Equals, Token::EqualsEquals),
+
                 // Complex tokens requiring multiple characters
                 _ if c.is_ascii_digit() => self.read_number(),
                 _ if c.is_ascii_alphabetic() => self.read_symbol(),
-                
+
                 // Invalid character handling
-                _ => Err(format!("Unexpected character: '{}'", c)),
+                _ => Err(CalcError::UnexpectedChar { ch: c, pos: start }),
             },
             None => Ok(Token::EndOfFile), // End of input
-        }
+        }?;
+
+        Ok((token, start))
     }
 
-    /// Reads numeric literal from input
-    fn read_number(&mut self) -> Result<Token, String> {
+    /// Reads a numeric literal from input, e.g. `42`, `3.14`, or `3i`
+    fn read_number(&mut self) -> Result<Token, CalcError> {
         let start = self.position;
-        
+
         // Consume all consecutive digits and decimal points
         while let Some(c) = self.current_char() {
             if !c.is_ascii_digit() && c != '.' {
@@ -58,19 +65,27 @@ impl Lexer {
             }
             self.advance();
         }
-        
-        // Parse collected characters into f64
-        let number_str: String = self.input[start..self.position].iter().collect();
-        number_str
+
+        // An `i` suffix makes this an imaginary literal rather than a real one
+        let is_imaginary = self.current_char() == Some('i');
+        if is_imaginary {
+            self.advance();
+        }
+
+        // Parse the digits (excluding the suffix) into f64
+        let number_end = if is_imaginary { self.position - 1 } else { self.position };
+        let number_str: String = self.input[start..number_end].iter().collect();
+        let value: f64 = number_str
             .parse()
-            .map(Token::Number)
-            .map_err(|_| format!("Invalid number format: '{}'", number_str))
+            .map_err(|_| CalcError::InvalidNumber { text: number_str, pos: start })?;
+
+        Ok(if is_imaginary { Token::Imaginary(value) } else { Token::Number(value) })
     }
 
     /// Reads symbol/identifier from input
-    fn read_symbol(&mut self) -> Result<Token, String> {
+    fn read_symbol(&mut self) -> Result<Token, CalcError> {
         let start = self.position;
-        
+
         // Consume all consecutive alphabetic characters
         while let Some(c) = self.current_char() {
             if !c.is_ascii_alphabetic() {
@@ -78,13 +93,13 @@ impl Lexer {
             }
             self.advance();
         }
-        
+
         let symbol: String = self.input[start..self.position].iter().collect();
         Ok(Token::Symbol(symbol))
     }
 
     // ================ HELPER METHODS ================
-    
+
     /// Returns current character without consuming it
     fn current_char(&self) -> Option<char> {
         self.input.get(self.position).copied()
@@ -96,11 +111,23 @@ impl Lexer {
     }
 
     /// Consumes current character and returns given token
-    fn consume(&mut self, token: Token) -> Result<Token, String> {
+    fn consume(&mut self, token: Token) -> Result<Token, CalcError> {
         self.advance();
         Ok(token)
     }
 
+    /// Consumes the current character, then also consumes a following `=` if
+    /// present - used for `<`/`<=`, `>`/`>=`, and `=`/`==`
+    fn consume_with_optional_equals(&mut self, single: Token, with_equals: Token) -> Result<Token, CalcError> {
+        self.advance();
+        if self.current_char() == Some('=') {
+            self.advance();
+            Ok(with_equals)
+        } else {
+            Ok(single)
+        }
+    }
+
     /// Skips whitespace characters
     fn skip_whitespace(&mut self) {
         while let Some(c) = self.current_char() {
@@ -4,21 +4,34 @@
 // Interactive Read-Eval-Print Loop for mathematical expressions
 // ===========================================
 
-use std::collections::HashMap;
 use std::io::{self, Write};
 
+use num_complex::Complex;
+
 mod ast;
+mod compiler;
+mod env;
+mod error;
+mod format;
 mod lexer;
 mod parser;
 mod token;
+mod vm;
 
+use crate::compiler::compile;
+use crate::env::Environment;
+use crate::error::{render_with_caret, CalcError};
+use crate::format::format_complex;
 use crate::parser::Parser;
+use crate::vm::Vm;
 
 /// Evaluates mathematical expression and returns result
-fn evaluate_expression(input: &str, env: &mut HashMap<String, f64>) -> Result<f64, String> {
+fn evaluate_expression(input: &str, env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+    env.printed = false;
     let mut parser = Parser::new(input)?;
     let ast = parser.parse()?;
-    ast.evaluate(env).ok_or_else(|| "Evaluation failed".to_string())
+    let instructions = compile(&*ast);
+    Vm::new().run(&instructions, env)
 }
 
 fn main() {
@@ -26,15 +39,20 @@ fn main() {
     println!("     RUST CALCULATOR REPL");
     println!("====================================");
     println!("Supported operations: + - * / ^");
+    println!("Comparisons: < > <= >= ==");
+    println!("Conditionals: if cond then a else b");
     println!("Functions: sin(x), cos(x), sqrt(x), print(x)");
-    println!("Variables: let x = 5, then use x in expressions");
+    println!("Variables: x = 5, then use x in expressions");
+    println!("Functions: square(x) = x^2, then call square(3)");
+    println!("Complex numbers: 3 + 4i, i, sqrt(-1)");
     println!("Type 'quit' to exit");
     println!("====================================\n");
 
     // Initialize environment with mathematical constants
-    let mut environment = HashMap::new();
-    environment.insert("pi".to_string(), std::f64::consts::PI);
-    environment.insert("e".to_string(), std::f64::consts::E);
+    let mut environment = Environment::new();
+    environment.variables.insert("pi".to_string(), Complex::new(std::f64::consts::PI, 0.0));
+    environment.variables.insert("e".to_string(), Complex::new(std::f64::consts::E, 0.0));
+    environment.variables.insert("i".to_string(), Complex::new(0.0, 1.0));
 
     // REPL loop
     loop {
@@ -61,14 +79,15 @@ fn main() {
                 // Evaluate expression and handle result
                 match evaluate_expression(input, &mut environment) {
                     Ok(result) => {
-                        // Result already printed if using print() function
-                        if !input.contains("print") {
-                            println!("=> {}", result);
+                        // Result already printed if evaluation routed through print()
+                        if !environment.printed {
+                            println!("=> {}", format_complex(result));
                         }
                     }
-                    Err(error) => {
-                        eprintln!("Error: {}", error);
-                    }
+                    Err(error) => match error.pos() {
+                        Some(pos) => eprintln!("Error: {}\n{}", error, render_with_caret(input, pos)),
+                        None => eprintln!("Error: {}", error),
+                    },
                 }
             }
         }
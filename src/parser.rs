@@ -4,111 +4,228 @@
 // Converts token stream into Abstract Syntax Tree
 // ===========================================
 
+use std::rc::Rc;
+
+use num_complex::Complex;
+
 use crate::ast::*;
+use crate::error::CalcError;
 use crate::lexer::Lexer;
-use crate::token::{Associativity, Token};
+use crate::token::{Associativity, Token, HIGHEST_PRECEDENCE};
 
 pub struct Parser {
     lexer: Lexer,
     current_token: Token,
-    next_token: Option<Token>, // One-token lookahead
+    current_pos: usize,
+    next_token: Option<(Token, usize)>, // One-token lookahead
 }
 
 impl Parser {
     /// Creates new parser with initialized token stream
-    pub fn new(input: &str) -> Result<Self, String> {
+    pub fn new(input: &str) -> Result<Self, CalcError> {
         let mut lexer = Lexer::new(input);
-        let current_token = lexer.next_token()?;
-        
+        let (current_token, current_pos) = lexer.next_token()?;
+
         Ok(Self {
             lexer,
             current_token,
+            current_pos,
             next_token: None,
         })
     }
 
     /// Parses entire expression and returns AST root
-    pub fn parse(&mut self) -> Result<Box<dyn AstNode>, String> {
+    pub fn parse(&mut self) -> Result<Box<dyn AstNode>, CalcError> {
         let ast = self.parse_expression(0)?; // Start with minimum precedence
         self.expect_end()?; // Ensure no extra tokens remain
         Ok(ast)
     }
 
     /// Recursive descent parser for expressions using precedence climbing
-    fn parse_expression(&mut self, min_precedence: u8) -> Result<Box<dyn AstNode>, String> {
-        // Parse left-hand side atom (number, variable, function call, etc.)
-        let mut left_expr = self.parse_atom()?;
-        
+    fn parse_expression(&mut self, min_precedence: u8) -> Result<Box<dyn AstNode>, CalcError> {
+        // Parse left-hand side atom (number, variable, function call, prefix op, etc.)
+        let mut left_expr = self.parse_unary()?;
+
         // Process operators with sufficient precedence
         while let Some((precedence, associativity)) = self.current_token.precedence_and_associativity() {
             if precedence < min_precedence {
                 break;
             }
-            
+
             let operator = self.current_token.clone();
             self.advance()?; // Consume operator
-            
+
             // Parse right-hand side with appropriate precedence
             let next_min_precedence = match associativity {
                 Associativity::Left => precedence + 1,
                 Associativity::Right => precedence,
             };
-            
+
             let right_expr = self.parse_expression(next_min_precedence)?;
             left_expr = self.create_binary_node(operator, left_expr, right_expr);
         }
-        
+
         Ok(left_expr)
     }
 
+    /// Parses an optional prefix `-`/`+` followed by an atom. The operand is
+    /// parsed at a precedence above every binary operator, so unary minus
+    /// binds tighter than `^`: `-2^2` parses as `(-2)^2`.
+    fn parse_unary(&mut self) -> Result<Box<dyn AstNode>, CalcError> {
+        match self.current_token {
+            Token::Minus => {
+                self.advance()?;
+                let operand = self.parse_expression(HIGHEST_PRECEDENCE + 1)?;
+                Ok(Box::new(NegateNode { operand }))
+            }
+            Token::Plus => {
+                self.advance()?;
+                self.parse_expression(HIGHEST_PRECEDENCE + 1)
+            }
+            _ => self.parse_atom(),
+        }
+    }
+
     /// Parses atomic expressions (leaf nodes or function calls)
-    fn parse_atom(&mut self) -> Result<Box<dyn AstNode>, String> {
+    fn parse_atom(&mut self) -> Result<Box<dyn AstNode>, CalcError> {
         match &self.current_token {
             Token::Number(value) => {
-                let node = Box::new(NumberNode { value: *value });
+                let node = Box::new(NumberNode { value: Complex::new(*value, 0.0) });
+                self.advance()?;
+                Ok(node)
+            }
+
+            Token::Imaginary(value) => {
+                let node = Box::new(NumberNode { value: Complex::new(0.0, *value) });
                 self.advance()?;
                 Ok(node)
             }
-            
+
+            Token::Symbol(name) if name == "if" => self.parse_if(),
+
             Token::Symbol(name) => {
                 let symbol_name = name.clone();
                 self.advance()?;
-                
+
                 match self.current_token {
-                    Token::LeftParenthesis => self.parse_function_call(symbol_name),
+                    Token::LeftParenthesis => self.parse_call_or_function_def(symbol_name),
                     Token::Equals => self.parse_assignment(symbol_name),
                     _ => Ok(Box::new(VariableNode { name: symbol_name })),
                 }
             }
-            
+
             Token::LeftParenthesis => {
                 self.advance()?; // Consume '('
                 let expr = self.parse_expression(0)?; // Parse inner expression
                 self.expect_token(Token::RightParenthesis)?; // Expect ')'
                 Ok(expr)
             }
-            
-            _ => Err(format!("Unexpected token: {:?}", self.current_token)),
+
+            _ => Err(CalcError::UnexpectedToken {
+                found: self.current_token.clone(),
+                expected: None,
+                pos: self.current_pos,
+            }),
         }
     }
 
-    /// Parses function calls (sin, cos, sqrt, print)
-    fn parse_function_call(&mut self, function_name: String) -> Result<Box<dyn AstNode>, String> {
+    /// Parses `if cond then a else b`
+    fn parse_if(&mut self) -> Result<Box<dyn AstNode>, CalcError> {
+        self.advance()?; // Consume 'if'
+        let condition = self.parse_expression(0)?;
+        self.expect_keyword("then")?;
+        let then_branch = self.parse_expression(0)?;
+        self.expect_keyword("else")?;
+        let else_branch = self.parse_expression(0)?;
+        Ok(Box::new(IfNode { condition, then_branch, else_branch }))
+    }
+
+    /// Verifies the current token is the symbol `keyword`, then advances
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), CalcError> {
+        match &self.current_token {
+            Token::Symbol(name) if name == keyword => self.advance(),
+            _ => Err(CalcError::UnexpectedToken {
+                found: self.current_token.clone(),
+                expected: Some(Token::Symbol(keyword.to_string())),
+                pos: self.current_pos,
+            }),
+        }
+    }
+
+    /// Parses a parenthesized, comma-separated list following a symbol, then
+    /// decides whether it's a function definition (`square(x) = x^2`) or a
+    /// call (`sin(x)`, `square(3)`, `hypot(a, b)`) based on whether an `=`
+    /// follows the closing `)` - both share the same `name(...)` prefix.
+    fn parse_call_or_function_def(&mut self, name: String) -> Result<Box<dyn AstNode>, CalcError> {
         self.expect_token(Token::LeftParenthesis)?;
-        let argument = self.parse_expression(0)?;
+
+        let mut arg_names = Vec::new(); // names of the bare-identifier args; used only if every arg is one
+        let mut args = Vec::new();
+        let mut all_identifiers = true;
+
+        if self.current_token != Token::RightParenthesis {
+            loop {
+                // An arg is a bare identifier only if the token after its symbol is `,` or `)`
+                if let Token::Symbol(symbol) = &self.current_token {
+                    let symbol = symbol.clone();
+                    let lookahead = self.lexer.next_token()?;
+                    if matches!(lookahead.0, Token::Comma | Token::RightParenthesis) {
+                        arg_names.push(symbol);
+                    } else {
+                        all_identifiers = false;
+                    }
+                    self.next_token = Some(lookahead);
+                } else {
+                    all_identifiers = false;
+                }
+
+                args.push(self.parse_expression(0)?);
+
+                if self.current_token == Token::Comma {
+                    self.advance()?;
+                } else {
+                    break;
+                }
+            }
+        }
+
         self.expect_token(Token::RightParenthesis)?;
-        
+
+        if self.current_token == Token::Equals {
+            if !all_identifiers {
+                return Err(CalcError::UnexpectedToken {
+                    found: self.current_token.clone(),
+                    expected: None,
+                    pos: self.current_pos,
+                });
+            }
+            self.advance()?; // Consume '='
+            let body = self.parse_expression(0)?;
+            return Ok(Box::new(FunctionDefNode { name, params: arg_names, body: Rc::from(body) }));
+        }
+
+        self.parse_function_call(name, args)
+    }
+
+    /// Resolves a call's arguments against a built-in function, falling back
+    /// to a user-defined function lookup at evaluation time
+    fn parse_function_call(&mut self, function_name: String, mut args: Vec<Box<dyn AstNode>>) -> Result<Box<dyn AstNode>, CalcError> {
         match function_name.as_str() {
-            "sin" => Ok(Box::new(SineNode { argument })),
-            "cos" => Ok(Box::new(CosineNode { argument })),
-            "sqrt" => Ok(Box::new(SquareRootNode { argument })),
-            "print" => Ok(Box::new(PrintNode { argument })),
-            _ => Err(format!("Unknown function: {}", function_name)),
+            "sin" | "cos" | "sqrt" | "print" if args.len() != 1 => Err(CalcError::ArityMismatch {
+                name: function_name,
+                expected: 1,
+                found: args.len(),
+            }),
+            "sin" => Ok(Box::new(SineNode { argument: args.remove(0) })),
+            "cos" => Ok(Box::new(CosineNode { argument: args.remove(0) })),
+            "sqrt" => Ok(Box::new(SquareRootNode { argument: args.remove(0) })),
+            "print" => Ok(Box::new(PrintNode { argument: args.remove(0) })),
+            _ => Ok(Box::new(FunctionCallNode { name: function_name, args })),
         }
     }
 
-    /// Parses variable assignments (let x = ...)
-    fn parse_assignment(&mut self, variable_name: String) -> Result<Box<dyn AstNode>, String> {
+    /// Parses variable assignments (x = ...)
+    fn parse_assignment(&mut self, variable_name: String) -> Result<Box<dyn AstNode>, CalcError> {
         self.expect_token(Token::Equals)?;
         let value_expr = self.parse_expression(0)?;
         Ok(Box::new(AssignmentNode {
@@ -118,7 +235,7 @@ impl Parser {
     }
 
     // ================ HELPER METHODS ================
-    
+
     /// Creates binary operation node from operator token
     fn create_binary_node(
         &self,
@@ -132,34 +249,145 @@ impl Parser {
             Token::Multiply => Box::new(MultiplyNode { left, right }),
             Token::Divide => Box::new(DivideNode { left, right }),
             Token::Caret => Box::new(PowerNode { base: left, exponent: right }),
+            Token::Less => Box::new(LessThanNode { left, right }),
+            Token::Greater => Box::new(GreaterThanNode { left, right }),
+            Token::LessEqual => Box::new(LessEqualNode { left, right }),
+            Token::GreaterEqual => Box::new(GreaterEqualNode { left, right }),
+            Token::EqualsEquals => Box::new(EqualsEqualsNode { left, right }),
             _ => panic!("Unsupported binary operator"),
         }
     }
 
     /// Advances to next token in stream
-    fn advance(&mut self) -> Result<(), String> {
-        self.current_token = match self.next_token.take() {
-            Some(token) => token,
+    fn advance(&mut self) -> Result<(), CalcError> {
+        let (token, pos) = match self.next_token.take() {
+            Some(token_and_pos) => token_and_pos,
             None => self.lexer.next_token()?,
         };
+        self.current_token = token;
+        self.current_pos = pos;
         Ok(())
     }
 
     /// Verifies current token matches expected, then advances
-    fn expect_token(&mut self, expected: Token) -> Result<(), String> {
+    fn expect_token(&mut self, expected: Token) -> Result<(), CalcError> {
         if self.current_token == expected {
             self.advance()
         } else {
-            Err(format!("Expected {:?}, found {:?}", expected, self.current_token))
+            Err(CalcError::UnexpectedToken {
+                found: self.current_token.clone(),
+                expected: Some(expected),
+                pos: self.current_pos,
+            })
         }
     }
 
     /// Ensures input is fully consumed
-    fn expect_end(&mut self) -> Result<(), String> {
+    fn expect_end(&mut self) -> Result<(), CalcError> {
         if self.current_token.is_eof() {
             Ok(())
         } else {
-            Err(format!("Unexpected token at end: {:?}", self.current_token))
+            Err(CalcError::UnexpectedToken {
+                found: self.current_token.clone(),
+                expected: None,
+                pos: self.current_pos,
+            })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Environment;
+    use num_complex::Complex;
+
+    fn eval(input: &str) -> Result<Complex<f64>, CalcError> {
+        let mut parser = Parser::new(input)?;
+        let ast = parser.parse()?;
+        let mut env = Environment::new();
+        ast.evaluate(&mut env)
+    }
+
+    /// Like `eval`, but threads a shared `Environment` through so later
+    /// statements can see variables/functions defined by earlier ones
+    fn eval_with(env: &mut Environment, input: &str) -> Result<Complex<f64>, CalcError> {
+        let mut parser = Parser::new(input)?;
+        let ast = parser.parse()?;
+        ast.evaluate(env)
+    }
+
+    #[test]
+    fn unexpected_character_reports_its_position() {
+        let err = eval("1 + @").unwrap_err();
+        assert_eq!(err, CalcError::UnexpectedChar { ch: '@', pos: 4 });
+    }
+
+    #[test]
+    fn missing_closing_paren_reports_unexpected_token() {
+        let err = eval("(1 + 2").unwrap_err();
+        assert!(matches!(err, CalcError::UnexpectedToken { expected: Some(Token::RightParenthesis), .. }));
+    }
+
+    #[test]
+    fn unary_minus_binds_tighter_than_power() {
+        // -2^2 parses as (-2)^2 = 4, not -(2^2) = -4
+        assert_eq!(eval("-2^2").unwrap(), Complex::new(4.0, 0.0));
+    }
+
+    #[test]
+    fn unary_plus_is_a_no_op() {
+        assert_eq!(eval("+3").unwrap(), Complex::new(3.0, 0.0));
+    }
+
+    #[test]
+    fn unary_minus_applies_to_a_parenthesized_expression() {
+        assert_eq!(eval("-(2 + 3)").unwrap(), Complex::new(-5.0, 0.0));
+    }
+
+    #[test]
+    fn comparisons_evaluate_to_one_or_zero() {
+        assert_eq!(eval("3 < 5").unwrap(), Complex::new(1.0, 0.0));
+        assert_eq!(eval("3 > 5").unwrap(), Complex::new(0.0, 0.0));
+        assert_eq!(eval("3 == 3").unwrap(), Complex::new(1.0, 0.0));
+        assert_eq!(eval("3 <= 3").unwrap(), Complex::new(1.0, 0.0));
+        assert_eq!(eval("3 >= 4").unwrap(), Complex::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn if_then_else_takes_the_matching_branch() {
+        assert_eq!(eval("if 1 < 2 then 10 else 20").unwrap(), Complex::new(10.0, 0.0));
+        assert_eq!(eval("if 1 > 2 then 10 else 20").unwrap(), Complex::new(20.0, 0.0));
+    }
+
+    #[test]
+    fn if_then_else_compiles_and_runs_identically_via_the_vm() {
+        let mut parser = Parser::new("if 2 == 2 then 1 + 1 else 9").unwrap();
+        let ast = parser.parse().unwrap();
+
+        let mut tree_env = Environment::new();
+        let tree_result = ast.evaluate(&mut tree_env).unwrap();
+
+        let instructions = crate::compiler::compile(&*ast);
+        let mut vm_env = Environment::new();
+        let vm_result = crate::vm::Vm::new().run(&instructions, &mut vm_env).unwrap();
+
+        assert_eq!(tree_result, Complex::new(2.0, 0.0));
+        assert_eq!(tree_result, vm_result);
+    }
+
+    #[test]
+    fn calling_user_function_with_wrong_arity_is_an_error() {
+        let mut env = Environment::new();
+        eval_with(&mut env, "square(x) = x^2").unwrap();
+        let err = eval_with(&mut env, "square(1, 2)").unwrap_err();
+        assert!(matches!(err, CalcError::ArityMismatch { expected: 1, found: 2, .. }));
+    }
+
+    #[test]
+    fn recursive_user_function_calls_work() {
+        let mut env = Environment::new();
+        eval_with(&mut env, "fact(n) = if n <= 1 then 1 else n * fact(n - 1)").unwrap();
+        assert_eq!(eval_with(&mut env, "fact(5)").unwrap(), Complex::new(120.0, 0.0));
+    }
+}
@@ -14,7 +14,14 @@ pub enum Token {
     Divide,             // /
     Caret,              // ^
     Equals,             // =
-    Number(f64),        // Numeric literal
+    Comma,              // ,
+    Less,               // <
+    Greater,            // >
+    LessEqual,          // <=
+    GreaterEqual,       // >=
+    EqualsEquals,       // ==
+    Number(f64),        // Real numeric literal
+    Imaginary(f64),     // Imaginary numeric literal, e.g. 3i
     Symbol(String),     // Identifier/function name
     EndOfFile,          // End of input marker
 }
@@ -26,14 +33,22 @@ pub enum Associativity {
     Right,  // Right-associative: a ^ b ^ c = a ^ (b ^ c)
 }
 
+/// Precedence of the tightest-binding binary operator (`^`). Prefix unary
+/// operators parse their operand one level above this, so they bind
+/// tighter than every binary operator (e.g. `-2^2` parses as `(-2)^2`).
+pub const HIGHEST_PRECEDENCE: u8 = 3;
+
 impl Token {
     /// Returns precedence and associativity for operator tokens
     /// Higher precedence = tighter binding
     pub fn precedence_and_associativity(&self) -> Option<(u8, Associativity)> {
         match self {
-            Token::Plus | Token::Minus => Some((1, Associativity::Left)),     // Lowest precedence
-            Token::Multiply | Token::Divide => Some((2, Associativity::Left)), // Medium precedence
-            Token::Caret => Some((3, Associativity::Right)),                  // Highest precedence
+            Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual | Token::EqualsEquals => {
+                Some((0, Associativity::Left)) // Lowest precedence - binds looser than arithmetic
+            }
+            Token::Plus | Token::Minus => Some((1, Associativity::Left)),
+            Token::Multiply | Token::Divide => Some((2, Associativity::Left)),
+            Token::Caret => Some((3, Associativity::Right)), // Highest precedence
             _ => None, // Non-operator tokens return None
         }
     }
@@ -0,0 +1,184 @@
+// ===========================================
+// STACK VIRTUAL MACHINE MODULE
+// ===========================================
+// Executes compiled bytecode against a shared variable environment
+// ===========================================
+
+use crate::compiler::{Instruction, UnaryFn};
+use crate::env::{Environment, FunctionDef};
+use crate::error::CalcError;
+use crate::format::format_complex;
+use num_complex::Complex;
+use std::rc::Rc;
+
+/// A small stack machine that runs a compiled `Vec<Instruction>`
+pub struct Vm {
+    stack: Vec<Complex<f64>>,
+}
+
+impl Vm {
+    /// Creates a new VM with an empty operand stack
+    pub fn new() -> Self {
+        Self { stack: Vec::new() }
+    }
+
+    /// Runs the instruction stream, returning the final top-of-stack value.
+    /// Uses an explicit program counter (rather than a plain iteration) so
+    /// `Jump`/`JumpIfZero` can redirect control flow for `if` expressions.
+    pub fn run(&mut self, instructions: &[Instruction], env: &mut Environment) -> Result<Complex<f64>, CalcError> {
+        let mut pc = 0;
+
+        while pc < instructions.len() {
+            match &instructions[pc] {
+                Instruction::Push(value) => self.stack.push(*value),
+                Instruction::Load(name) => {
+                    let value = *env
+                        .variables
+                        .get(name)
+                        .ok_or_else(|| CalcError::UndefinedVariable(name.clone()))?;
+                    self.stack.push(value);
+                }
+                Instruction::Store(name) => {
+                    let value = self.peek()?;
+                    env.variables.insert(name.clone(), value);
+                }
+                Instruction::Add => self.binary_op(|a, b| a + b)?,
+                Instruction::Sub => self.binary_op(|a, b| a - b)?,
+                Instruction::Mul => self.binary_op(|a, b| a * b)?,
+                Instruction::Div => {
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    if right == Complex::new(0.0, 0.0) {
+                        return Err(CalcError::DivisionByZero);
+                    }
+                    self.stack.push(left / right);
+                }
+                // Real base raised to a real exponent: fall back to `powf` for
+                // exact results - but only when `powf` itself stays real
+                // (non-negative base, or an integer exponent). A negative base
+                // with a fractional exponent must go through `powc` for the
+                // correct principal complex root; `powf` would just return `NaN`.
+                Instruction::Pow => self.binary_op(|base, exp| {
+                    if base.im == 0.0 && exp.im == 0.0 && (base.re >= 0.0 || exp.re.fract() == 0.0) {
+                        Complex::new(base.re.powf(exp.re), 0.0)
+                    } else {
+                        base.powc(exp)
+                    }
+                })?,
+                Instruction::Neg => {
+                    let negated = -self.pop()?;
+                    // See the matching note in `ast::NegateNode::evaluate`:
+                    // avoid a signed zero flipping `sqrt`/`powc`'s branch cut
+                    self.stack.push(Complex::new(
+                        if negated.re == 0.0 { 0.0 } else { negated.re },
+                        if negated.im == 0.0 { 0.0 } else { negated.im },
+                    ));
+                }
+                // Complex numbers have no natural ordering, so `<`/`>`/`<=`/`>=`
+                // compare real parts only; `==` compares the full value.
+                Instruction::Less => self.comparison_op(|a, b| a.re < b.re)?,
+                Instruction::Greater => self.comparison_op(|a, b| a.re > b.re)?,
+                Instruction::LessEqual => self.comparison_op(|a, b| a.re <= b.re)?,
+                Instruction::GreaterEqual => self.comparison_op(|a, b| a.re >= b.re)?,
+                Instruction::Equal => self.comparison_op(|a, b| a == b)?,
+                Instruction::Call(function) => {
+                    let argument = self.pop()?;
+                    self.stack.push(match function {
+                        UnaryFn::Sin => argument.sin(),
+                        UnaryFn::Cos => argument.cos(),
+                        UnaryFn::Sqrt => argument.sqrt(),
+                    });
+                }
+                Instruction::Print => {
+                    println!("=> {}", format_complex(self.peek()?));
+                    env.printed = true;
+                }
+                Instruction::DefineFunction { name, params, body } => {
+                    env.functions.insert(
+                        name.clone(),
+                        Rc::new(FunctionDef { params: params.clone(), body: Rc::clone(body) }),
+                    );
+                    self.stack.push(Complex::new(0.0, 0.0));
+                }
+                Instruction::CallFunction { name, arg_count } => {
+                    let function = env
+                        .functions
+                        .get(name)
+                        .cloned()
+                        .ok_or_else(|| CalcError::UnknownFunction(name.clone()))?;
+
+                    if *arg_count != function.params.len() {
+                        return Err(CalcError::ArityMismatch {
+                            name: name.clone(),
+                            expected: function.params.len(),
+                            found: *arg_count,
+                        });
+                    }
+
+                    let mut args = Vec::with_capacity(*arg_count);
+                    for _ in 0..*arg_count {
+                        args.push(self.pop()?);
+                    }
+                    args.reverse(); // popped in reverse order of evaluation
+
+                    let mut locals = env.variables.clone();
+                    for (param, value) in function.params.iter().zip(args) {
+                        locals.insert(param.clone(), value);
+                    }
+
+                    let mut call_env = Environment { variables: locals, functions: env.functions.clone(), printed: false };
+                    let result = function.body.evaluate(&mut call_env)?;
+                    env.printed |= call_env.printed;
+                    self.stack.push(result);
+                }
+                Instruction::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Instruction::JumpIfZero(target) => {
+                    if self.pop()? == Complex::new(0.0, 0.0) {
+                        pc = *target;
+                        continue;
+                    }
+                }
+            }
+
+            pc += 1;
+        }
+
+        self.peek()
+    }
+
+    /// Pops the top two operands, applies `op`, and pushes the result
+    fn binary_op(&mut self, op: impl Fn(Complex<f64>, Complex<f64>) -> Complex<f64>) -> Result<(), CalcError> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        self.stack.push(op(left, right));
+        Ok(())
+    }
+
+    /// Pops the top two operands, applies the comparison, and pushes 1.0/0.0
+    fn comparison_op(&mut self, cmp: impl Fn(Complex<f64>, Complex<f64>) -> bool) -> Result<(), CalcError> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        self.stack.push(if cmp(left, right) { Complex::new(1.0, 0.0) } else { Complex::new(0.0, 0.0) });
+        Ok(())
+    }
+
+    /// Pops the top of the stack - the compiler only ever emits balanced
+    /// instruction streams, so an empty stack here means a compiler bug
+    fn pop(&mut self) -> Result<Complex<f64>, CalcError> {
+        Ok(self.stack.pop().expect("stack underflow: malformed bytecode"))
+    }
+
+    /// Reads the top of the stack without consuming it (see `pop`)
+    fn peek(&self) -> Result<Complex<f64>, CalcError> {
+        Ok(*self.stack.last().expect("stack underflow: malformed bytecode"))
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}